@@ -1,33 +1,65 @@
 //! Uho Backfill Sidecar
 //!
 //! Streams historical Solana transactions from Old Faithful via Jetstreamer,
-//! filters by program ID, and emits NDJSON to stdout for the Node.js consumer.
+//! filters by program ID(s)/mentions, and emits NDJSON to stdout for the
+//! Node.js consumer.
 //!
 //! Usage:
-//!   uho-backfill --program <PROGRAM_ID> --start-slot <SLOT> --end-slot <SLOT> [--threads N]
+//!   uho-backfill --program <PROGRAM_ID>[,<PROGRAM_ID>...] --start-slot <SLOT> --end-slot <SLOT> [--threads N] [--checkpoint <PATH>]
+//!   uho-backfill --mentions <ADDRESS>[,<ADDRESS>...] --start-slot <SLOT> --end-slot <SLOT>
+//!   uho-backfill --all --start-slot <SLOT> --end-slot <SLOT>
 //!
-//! Each stdout line is a JSON object:
+//! By default each matched record is written as an NDJSON line to stdout:
 //!   {"signature":"...","slot":123,"blockTime":456,"logs":["Program log: ..."]}
+//! `--output csv` writes CSV rows to `--out-file` instead, and `--socket`
+//! additionally streams NDJSON lines to any client connected to a Unix
+//! domain socket. With `--idl <path>`, `Program data:` log lines are also
+//! decoded into the record's `events` field.
 //!
-//! Progress stats are written to stderr.
+//! A `PROGRESS:` line is written to stderr every 100k transactions, and a
+//! `STATS:` line every `--stats-interval-secs` (default 30) with throughput,
+//! ETA, and per-transaction latency percentiles. Once the run finishes, a
+//! `GAPS:` line reports any slots in range that firehose appears not to have
+//! delivered.
 
+use std::fs;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+use base64::Engine;
 use clap::Parser;
 use futures_util::FutureExt;
 use jetstreamer::firehose::TransactionData;
 use jetstreamer::plugin::{Plugin, PluginFuture};
 use jetstreamer::JetstreamerRunner;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
 /// CLI arguments
 #[derive(Parser, Debug)]
-#[command(name = "uho-backfill", about = "Stream historical Solana transactions filtered by program ID")]
+#[command(name = "uho-backfill", about = "Stream historical Solana transactions filtered by program ID(s), mentions, or all")]
 struct Args {
-    /// Solana program ID (base58) to filter transactions by
+    /// Solana program ID (base58) to filter transactions by. May be repeated
+    /// or comma-separated to match any of several programs (any-of).
+    #[arg(long, value_delimiter = ',')]
+    program: Vec<String>,
+
+    /// Arbitrary account address (base58) to match transactions against, in
+    /// addition to `--program`. May be repeated or comma-separated. Mirrors
+    /// the `mentions` filter accepted by Solana RPC's logsSubscribe.
+    #[arg(long, value_delimiter = ',')]
+    mentions: Vec<String>,
+
+    /// Disable the program/mentions filter and match every non-vote
+    /// transaction. Mirrors RPC's `all` logs filter.
+    #[arg(long)]
+    all: bool,
+
+    /// Disable the program/mentions filter and match every transaction,
+    /// including vote transactions. Mirrors RPC's `allWithVotes` logs filter.
     #[arg(long)]
-    program: String,
+    all_with_votes: bool,
 
     /// Starting slot (inclusive)
     #[arg(long)]
@@ -40,9 +72,153 @@ struct Args {
     /// Number of firehose threads (auto-detected if omitted)
     #[arg(long, default_value = "4")]
     threads: usize,
+
+    /// Path to a checkpoint file used to make the backfill resumable. When
+    /// present at startup, `start_slot` is advanced to the last committed
+    /// watermark + 1. Updated periodically while running.
+    #[arg(long)]
+    checkpoint: Option<PathBuf>,
+
+    /// Primary output format for matched records.
+    #[arg(long, value_enum, default_value = "ndjson")]
+    output: OutputFormat,
+
+    /// Destination file for `--output csv`. Required when `--output csv` is set.
+    #[arg(long)]
+    out_file: Option<PathBuf>,
+
+    /// Unix domain socket path to stream matched records to, in addition to
+    /// `--output`. Any client connected to the socket receives each record
+    /// as an NDJSON line in real time.
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Path to an Anchor IDL file. When set, `Program data:` log lines are
+    /// decoded into structured events (see `OutputRecord::events`) instead
+    /// of being left for the consumer to parse.
+    #[arg(long)]
+    idl: Option<PathBuf>,
+
+    /// Wall-clock interval, in seconds, between structured `STATS:` reports
+    /// on stderr (throughput, ETA, and per-transaction latency percentiles).
+    #[arg(long, default_value = "30")]
+    stats_interval_secs: u64,
+}
+
+/// Primary output format for matched records.
+#[derive(clap::ValueEnum, Clone, Debug)]
+enum OutputFormat {
+    Ndjson,
+    Csv,
+}
+
+/// Which transactions to match, mirroring the filter vocabulary of Solana
+/// RPC's `logsSubscribe` (`RpcTransactionLogsFilter`): either every
+/// account address of interest (programs and/or arbitrary mentions), or an
+/// unfiltered firehose with or without vote transactions.
+enum Filter {
+    All,
+    AllWithVotes,
+    /// Matches transactions whose `static_account_keys()` contains any of
+    /// `programs` or `mentions`. Kept as separate lists (rather than one
+    /// combined `Vec`) because only `programs` are expected to also appear
+    /// as literal base58 text inside `log_messages` (e.g. "Program <id>
+    /// invoke [1]") — arbitrary mentioned accounts (wallets, token
+    /// accounts, ...) essentially never do, so the extra log-text check
+    /// only makes sense for `programs`.
+    Mentions {
+        programs: Vec<[u8; 32]>,
+        mentions: Vec<[u8; 32]>,
+    },
+}
+
+impl Filter {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        if args.all_with_votes {
+            return Ok(Filter::AllWithVotes);
+        }
+        if args.all {
+            return Ok(Filter::All);
+        }
+        if args.program.is_empty() && args.mentions.is_empty() {
+            return Err(
+                "one of --program, --mentions, --all, or --all-with-votes is required".into(),
+            );
+        }
+        let programs = args
+            .program
+            .iter()
+            .map(|addr| decode_address(addr))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mentions = args
+            .mentions
+            .iter()
+            .map(|addr| decode_address(addr))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Filter::Mentions { programs, mentions })
+    }
+}
+
+/// Decode a base58 Solana address into its 32-byte representation.
+fn decode_address(address: &str) -> Result<[u8; 32], String> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| format!("invalid base58 address {address:?}: {e}"))?;
+    if bytes.len() != 32 {
+        return Err(format!("address {address:?} is not 32 bytes"));
+    }
+    let mut arr = [0u8; 32];
+    arr.copy_from_slice(&bytes);
+    Ok(arr)
 }
 
-/// NDJSON output record written to stdout
+/// Returns `(matched_program, matched_mention)`: whether `account_keys`
+/// contains any address from `programs` / `mentions` respectively. Pulled out
+/// of `on_transaction`'s `Filter::Mentions` arm so this matching logic can be
+/// unit tested without a real `TransactionData`.
+fn classify_mentions_match(
+    programs: &[[u8; 32]],
+    mentions: &[[u8; 32]],
+    account_keys: impl Iterator<Item = [u8; 32]> + Clone,
+) -> (bool, bool) {
+    let matched_program = account_keys
+        .clone()
+        .any(|key| programs.iter().any(|addr| key == *addr));
+    let matched_mention = account_keys.any(|key| mentions.iter().any(|addr| key == *addr));
+    (matched_program, matched_mention)
+}
+
+/// Persisted checkpoint state, written periodically during the backfill.
+///
+/// `watermark_slot` is the highest slot for which every slot in
+/// `[start_slot, watermark_slot]` has been fully processed by every thread,
+/// i.e. the min of each thread's last-completed slot. Because jetstreamer
+/// delivers slots out of order across threads, only this contiguous prefix
+/// can be safely resumed from.
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    watermark_slot: u64,
+    processed: u64,
+    matched: u64,
+}
+
+impl Checkpoint {
+    fn load(path: &PathBuf) -> Option<Self> {
+        let data = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn save(&self, path: &PathBuf) -> std::io::Result<()> {
+        let data = serde_json::to_string(self)?;
+        // Write to a temp file and rename so a crash mid-write never leaves
+        // a truncated/corrupt checkpoint behind.
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+/// Output record for a matched transaction, fanned out to every active sink.
 #[derive(Serialize)]
 struct OutputRecord {
     signature: String,
@@ -50,28 +226,829 @@ struct OutputRecord {
     #[serde(rename = "blockTime")]
     block_time: Option<i64>,
     logs: Vec<String>,
+    /// Structured events decoded from `logs` via `--idl`. Empty when no IDL
+    /// was supplied or no log line matched a known event discriminator.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    events: Vec<DecodedEvent>,
 }
 
-/// Plugin that filters transactions by program ID and emits matching ones as NDJSON
-struct ProgramFilterPlugin {
-    program_id_bytes: [u8; 32],
+/// A single Anchor event decoded from a `Program data:` log line.
+#[derive(Serialize)]
+struct DecodedEvent {
+    name: String,
+    fields: serde_json::Value,
+    /// `true` if `fields` stopped short of every field the IDL declares,
+    /// because `decode_field` could not decode one of them: its type is one
+    /// it doesn't know (vec/option/defined/enum/...), or the remaining bytes
+    /// are too short or invalid for that type. The undecodable field's raw
+    /// remaining bytes are included in `fields` as `"<name>_raw"` (hex)
+    /// instead of the event being dropped entirely.
+    #[serde(skip_serializing_if = "is_false")]
+    partial: bool,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
+}
+
+/// Minimal subset of an Anchor IDL file needed to decode self-CPI log events.
+#[derive(Deserialize)]
+struct Idl {
+    #[serde(default)]
+    events: Vec<IdlEvent>,
+}
+
+#[derive(Deserialize, Clone)]
+struct IdlEvent {
+    name: String,
+    /// Explicit 8-byte discriminator, present in modern Anchor IDLs. Older
+    /// (pre-0.30) IDLs omit this; when absent it's derived from the event
+    /// name via `anchor_event_sighash`, matching what the Anchor client
+    /// generator computes at runtime.
+    #[serde(default)]
+    discriminator: Option<[u8; 8]>,
+    #[serde(default)]
+    fields: Vec<IdlField>,
+}
+
+#[derive(Deserialize, Clone)]
+struct IdlField {
+    name: String,
+    #[serde(rename = "type")]
+    ty: IdlFieldType,
+}
+
+/// Anchor field types. A type given as a plain string (`"u64"`, `"string"`,
+/// ...) is `Named`, whether or not `decode_field` knows it; anything else
+/// (vecs, options, defined/struct types) falls back to `Other`.
+/// `decode_field` returns `None` for `Other` and unknown names, and `decode_event` reports the field's raw remaining
+/// bytes instead of guessing at its layout.
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+enum IdlFieldType {
+    Named(String),
+    Other(serde_json::Value),
+}
+
+/// Derives the 8-byte Anchor event discriminator used when an IDL doesn't
+/// embed one explicitly: the first 8 bytes of `sha256("event:<name>")`,
+/// matching Anchor's client-side sighash computation.
+fn anchor_event_sighash(name: &str) -> [u8; 8] {
+    let digest = Sha256::digest(format!("event:{name}").as_bytes());
+    let mut sighash = [0u8; 8];
+    sighash.copy_from_slice(&digest[..8]);
+    sighash
+}
+
+/// Decodes `Program data:` log lines against a loaded IDL's event
+/// discriminators. Built once at startup from `--idl`.
+struct EventDecoder {
+    events_by_discriminator: std::collections::HashMap<[u8; 8], IdlEvent>,
+}
+
+impl EventDecoder {
+    fn load(path: &PathBuf) -> Result<Self, String> {
+        let data = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read IDL {path:?}: {e}"))?;
+        let idl: Idl = serde_json::from_str(&data)
+            .map_err(|e| format!("failed to parse IDL {path:?}: {e}"))?;
+        let events_by_discriminator = idl
+            .events
+            .into_iter()
+            .map(|event| {
+                let discriminator = event
+                    .discriminator
+                    .unwrap_or_else(|| anchor_event_sighash(&event.name));
+                (discriminator, event)
+            })
+            .collect();
+        Ok(Self {
+            events_by_discriminator,
+        })
+    }
+
+    /// Scan `logs` for `Program data:` lines and decode any that match a
+    /// known event discriminator. Lines that don't decode at all (unknown
+    /// discriminator, truncated/invalid base64) are silently skipped; the
+    /// raw log text is still preserved in `OutputRecord::logs`. Events with
+    /// an unsupported field type are still returned, marked `partial`, per
+    /// `DecodedEvent::partial`.
+    fn decode_logs(&self, logs: &[String]) -> Vec<DecodedEvent> {
+        logs.iter()
+            .filter_map(|log| log.strip_prefix("Program data: "))
+            .filter_map(|encoded| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded.trim())
+                    .ok()
+            })
+            .filter_map(|bytes| self.decode_event(&bytes))
+            .collect()
+    }
+
+    fn decode_event(&self, bytes: &[u8]) -> Option<DecodedEvent> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let (discriminator, mut rest) = bytes.split_at(8);
+        let discriminator: [u8; 8] = discriminator.try_into().ok()?;
+        let event = self.events_by_discriminator.get(&discriminator)?;
+
+        let mut fields = serde_json::Map::new();
+        let mut partial = false;
+        for field in &event.fields {
+            match decode_field(&field.ty, &mut rest) {
+                Some(value) => {
+                    fields.insert(field.name.clone(), value);
+                }
+                None => {
+                    // Unsupported type or bad data: we don't know how many
+                    // bytes it consumes, so further fields can't be reliably located
+                    // either. Surface what's left raw instead of dropping
+                    // the whole event.
+                    fields.insert(
+                        format!("{}_raw", field.name),
+                        serde_json::Value::String(hex_encode(rest)),
+                    );
+                    partial = true;
+                    break;
+                }
+            }
+        }
+
+        Some(DecodedEvent {
+            name: event.name.clone(),
+            fields: serde_json::Value::Object(fields),
+            partial,
+        })
+    }
+}
+
+/// Encodes `bytes` as lowercase hex, for the raw fallback in
+/// `EventDecoder::decode_event`.
+fn hex_encode(bytes: &[u8]) -> String {
+    use std::fmt::Write;
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(out, "{byte:02x}").expect("writing to a String never fails");
+    }
+    out
+}
+
+/// Decodes a single Borsh-encoded field from `cursor`, advancing it past the
+/// bytes consumed. Returns `None` on an unsupported or out-of-bounds type
+/// rather than guessing; the caller (`EventDecoder::decode_event`) reports
+/// the remaining raw bytes instead of dropping the whole event.
+fn decode_field(ty: &IdlFieldType, cursor: &mut &[u8]) -> Option<serde_json::Value> {
+    let IdlFieldType::Named(name) = ty else {
+        return None;
+    };
+
+    macro_rules! take_int {
+        ($int:ty) => {{
+            const LEN: usize = std::mem::size_of::<$int>();
+            if cursor.len() < LEN {
+                return None;
+            }
+            let (head, tail) = cursor.split_at(LEN);
+            *cursor = tail;
+            <$int>::from_le_bytes(head.try_into().ok()?)
+        }};
+    }
+
+    Some(match name.as_str() {
+        "bool" => {
+            if cursor.is_empty() {
+                return None;
+            }
+            let (head, tail) = cursor.split_at(1);
+            *cursor = tail;
+            serde_json::Value::Bool(head[0] != 0)
+        }
+        "u8" => take_int!(u8).into(),
+        "i8" => take_int!(i8).into(),
+        "u16" => take_int!(u16).into(),
+        "i16" => take_int!(i16).into(),
+        "u32" => take_int!(u32).into(),
+        "i32" => take_int!(i32).into(),
+        "u64" => take_int!(u64).to_string().into(),
+        "i64" => take_int!(i64).to_string().into(),
+        "f32" => take_int!(f32).into(),
+        "f64" => take_int!(f64).into(),
+        "string" => {
+            let len = take_int!(u32) as usize;
+            if cursor.len() < len {
+                return None;
+            }
+            let (head, tail) = cursor.split_at(len);
+            *cursor = tail;
+            serde_json::Value::String(String::from_utf8(head.to_vec()).ok()?)
+        }
+        "publicKey" | "pubkey" => {
+            if cursor.len() < 32 {
+                return None;
+            }
+            let (head, tail) = cursor.split_at(32);
+            *cursor = tail;
+            serde_json::Value::String(bs58::encode(head).into_string())
+        }
+        _ => return None,
+    })
+}
+
+/// A destination that matched records are written to. Implementations run
+/// on a single dedicated writer task (see `spawn_sink_writer`), so they
+/// don't need to be `Sync` and can buffer/own their IO handle freely.
+trait OutputSink: Send {
+    fn write_record<'a>(
+        &'a mut self,
+        record: &'a OutputRecord,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send + 'a>>;
+}
+
+/// Writes one NDJSON object per line to stdout.
+struct NdjsonStdoutSink {
+    stdout: tokio::io::Stdout,
+}
+
+impl NdjsonStdoutSink {
+    fn new() -> Self {
+        Self {
+            stdout: tokio::io::stdout(),
+        }
+    }
+}
+
+impl OutputSink for NdjsonStdoutSink {
+    fn write_record<'a>(
+        &'a mut self,
+        record: &'a OutputRecord,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send + 'a>> {
+        use tokio::io::AsyncWriteExt;
+        Box::pin(async move {
+            let mut line = serde_json::to_string(record).expect("OutputRecord is always valid JSON");
+            line.push('\n');
+            self.stdout.write_all(line.as_bytes()).await
+        })
+    }
+}
+
+/// Writes one CSV row per matched transaction to a file, joining the log
+/// lines of each transaction into a single escaped field. Decoded `--idl`
+/// events (see `OutputRecord::events`) are JSON-encoded into their own
+/// column rather than dropped, so `--output csv --idl ...` doesn't silently
+/// throw away the decode work.
+struct CsvFileSink {
+    file: tokio::fs::File,
+}
+
+impl CsvFileSink {
+    async fn create(path: &PathBuf) -> std::io::Result<Self> {
+        let mut file = tokio::fs::File::create(path).await?;
+        use tokio::io::AsyncWriteExt;
+        file.write_all(b"signature,slot,blockTime,logs,events\n").await?;
+        Ok(Self { file })
+    }
+}
+
+/// Wraps `field` in double quotes, doubling any quotes inside it, per RFC 4180.
+fn csv_escape(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+impl OutputSink for CsvFileSink {
+    fn write_record<'a>(
+        &'a mut self,
+        record: &'a OutputRecord,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send + 'a>> {
+        use tokio::io::AsyncWriteExt;
+        Box::pin(async move {
+            let joined_logs = record.logs.join("; ");
+            let events_json = if record.events.is_empty() {
+                String::new()
+            } else {
+                serde_json::to_string(&record.events).expect("DecodedEvent is always valid JSON")
+            };
+            let row = format!(
+                "{},{},{},{},{}\n",
+                csv_escape(&record.signature),
+                record.slot,
+                record
+                    .block_time
+                    .map(|t| t.to_string())
+                    .unwrap_or_default(),
+                csv_escape(&joined_logs),
+                csv_escape(&events_json),
+            );
+            self.file.write_all(row.as_bytes()).await
+        })
+    }
+}
+
+/// Max time to wait for a single client write before treating it as stalled
+/// and dropping the connection. `SocketSink` shares a writer task with every
+/// other sink (see `spawn_sink_writer`), so a client that stops reading
+/// (buffer full, no disconnect) must not be allowed to block that task's
+/// `write_record` call indefinitely — that would also stall the NDJSON/CSV
+/// sinks running alongside it.
+const SOCKET_CLIENT_WRITE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Streams each matched record as an NDJSON line to every client currently
+/// connected to a Unix domain socket, for local real-time consumers (e.g. a
+/// block explorer). A background task accepts new connections and appends
+/// them to `clients`; dead or stalled (see `SOCKET_CLIENT_WRITE_TIMEOUT`)
+/// connections are dropped on the next failed/timed-out write.
+struct SocketSink {
+    clients: Arc<tokio::sync::Mutex<Vec<tokio::net::UnixStream>>>,
+}
+
+impl SocketSink {
+    async fn bind(path: &PathBuf) -> std::io::Result<Self> {
+        // Remove a stale socket file from a previous run, if any.
+        let _ = std::fs::remove_file(path);
+        let listener = tokio::net::UnixListener::bind(path)?;
+        let clients: Arc<tokio::sync::Mutex<Vec<tokio::net::UnixStream>>> = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+
+        let accept_clients = clients.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _addr)) => accept_clients.lock().await.push(stream),
+                    Err(err) => {
+                        eprintln!("WARN: socket sink accept failed: {}", err);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self { clients })
+    }
+}
+
+impl OutputSink for SocketSink {
+    fn write_record<'a>(
+        &'a mut self,
+        record: &'a OutputRecord,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send + 'a>> {
+        use tokio::io::AsyncWriteExt;
+        Box::pin(async move {
+            let mut line = serde_json::to_string(record).expect("OutputRecord is always valid JSON");
+            line.push('\n');
+            let mut clients = self.clients.lock().await;
+            let mut i = 0;
+            while i < clients.len() {
+                let write = clients[i].write_all(line.as_bytes());
+                match tokio::time::timeout(SOCKET_CLIENT_WRITE_TIMEOUT, write).await {
+                    Ok(Ok(())) => i += 1,
+                    Ok(Err(_)) => {
+                        clients.remove(i);
+                    }
+                    Err(_timed_out) => {
+                        eprintln!(
+                            "WARN: socket sink client write exceeded {:?}, dropping connection",
+                            SOCKET_CLIENT_WRITE_TIMEOUT
+                        );
+                        clients.remove(i);
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Spawns the single writer task that every firehose thread's matched
+/// records funnel through via `tx`, removing the stdout-lock contention of
+/// writing directly from each thread. Returns the task's `JoinHandle`
+/// alongside the sender so the caller can drain it before exiting: once
+/// every sender clone is dropped, `rx.recv()` returns `None`, the task
+/// exits, and the handle resolves.
+fn spawn_sink_writer(
+    mut sinks: Vec<Box<dyn OutputSink>>,
+) -> (
+    tokio::sync::mpsc::UnboundedSender<OutputRecord>,
+    tokio::task::JoinHandle<()>,
+) {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<OutputRecord>();
+    let handle = tokio::spawn(async move {
+        while let Some(record) = rx.recv().await {
+            for sink in sinks.iter_mut() {
+                if let Err(err) = sink.write_record(&record).await {
+                    eprintln!("WARN: output sink write failed: {}", err);
+                }
+            }
+        }
+    });
+    (tx, handle)
+}
+
+/// Spawns a background task that, every `interval_secs`, emits a structured
+/// `STATS:` line to stderr with throughput (tx/s, matches/s, slots/s), ETA
+/// against `end_slot`, and per-transaction latency percentiles merged from
+/// `stats.latency`. Runs until the process exits.
+///
+/// Progress/ETA are anchored on `stats.commit_watermark()` rather than
+/// `stats.last_slot`: `last_slot` is just whichever thread most recently
+/// stored a slot, which isn't monotonic (threads process different,
+/// possibly far-apart slot ranges concurrently and out of order), while
+/// `commit_watermark()` is monotonic by construction and already doubles as
+/// the checkpointing progress measure.
+fn spawn_stats_reporter(stats: Arc<PluginStats>, start_slot: u64, end_slot: u64, interval_secs: u64) {
+    if interval_secs == 0 {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+        // The first tick fires immediately; skip it so the first report
+        // reflects a full interval of activity.
+        ticker.tick().await;
+
+        let mut last_tx_count = 0u64;
+        let mut last_match_count = 0u64;
+        let mut last_slot = start_slot;
+
+        loop {
+            ticker.tick().await;
+
+            let tx_count = stats.tx_count.load(Ordering::Relaxed);
+            let match_count = stats.match_count.load(Ordering::Relaxed);
+            let current_slot = stats.commit_watermark().max(last_slot);
+
+            let tx_per_sec = (tx_count - last_tx_count) as f64 / interval_secs as f64;
+            let matches_per_sec = (match_count - last_match_count) as f64 / interval_secs as f64;
+            let slots_per_sec = (current_slot - last_slot) as f64 / interval_secs as f64;
+
+            let remaining_slots = end_slot.saturating_sub(current_slot);
+            let eta_secs = if slots_per_sec > 0.0 {
+                Some((remaining_slots as f64 / slots_per_sec).round() as u64)
+            } else {
+                None
+            };
+
+            let p = stats.latency.percentiles();
+
+            eprintln!(
+                "STATS:{{\"txPerSec\":{:.1},\"matchesPerSec\":{:.1},\"slotsPerSec\":{:.1},\"currentSlot\":{},\"targetSlot\":{},\"etaSecs\":{},\"latencyMicros\":{{\"p50\":{},\"p90\":{},\"p99\":{}}}}}",
+                tx_per_sec,
+                matches_per_sec,
+                slots_per_sec,
+                current_slot,
+                end_slot,
+                eta_secs.map(|s| s.to_string()).unwrap_or_else(|| "null".to_string()),
+                p.p50,
+                p.p90,
+                p.p99,
+            );
+
+            last_tx_count = tx_count;
+            last_match_count = match_count;
+            last_slot = current_slot;
+        }
+    });
+}
+
+/// Upper bound (in microseconds) of each latency histogram bucket. The final
+/// implicit bucket catches anything slower than the last boundary.
+const LATENCY_BUCKETS_MICROS: &[u64] = &[
+    50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 500_000, 1_000_000,
+];
+
+/// Per-thread latency histogram for `on_transaction` processing time. Each
+/// thread accumulates into its own bucket counters (no cross-thread
+/// contention); `percentiles()` merges them at report time. Mirrors the
+/// approach of a simple sharded histogram rather than a single contended one.
+struct LatencyHistogram {
+    thread_buckets: Vec<Vec<AtomicU64>>,
+}
+
+/// p50/p90/p99 processing-time percentiles, in microseconds.
+struct LatencyPercentiles {
+    p50: u64,
+    p90: u64,
+    p99: u64,
+}
+
+impl LatencyHistogram {
+    fn new(threads: usize) -> Self {
+        let bucket_count = LATENCY_BUCKETS_MICROS.len() + 1;
+        Self {
+            thread_buckets: (0..threads)
+                .map(|_| (0..bucket_count).map(|_| AtomicU64::new(0)).collect())
+                .collect(),
+        }
+    }
+
+    fn record(&self, thread_id: usize, micros: u64) {
+        let Some(buckets) = self.thread_buckets.get(thread_id) else {
+            eprintln!(
+                "WARN: thread_id {} is out of range for {} configured threads; latency sample dropped",
+                thread_id,
+                self.thread_buckets.len()
+            );
+            return;
+        };
+        let bucket = LATENCY_BUCKETS_MICROS
+            .iter()
+            .position(|&upper_bound| micros <= upper_bound)
+            .unwrap_or(LATENCY_BUCKETS_MICROS.len());
+        buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn percentiles(&self) -> LatencyPercentiles {
+        let bucket_count = LATENCY_BUCKETS_MICROS.len() + 1;
+        let mut merged = vec![0u64; bucket_count];
+        for buckets in &self.thread_buckets {
+            for (i, count) in buckets.iter().enumerate() {
+                merged[i] += count.load(Ordering::Relaxed);
+            }
+        }
+        let total: u64 = merged.iter().sum();
+
+        let percentile = |p: f64| -> u64 {
+            if total == 0 {
+                return 0;
+            }
+            let target = (total as f64 * p).ceil() as u64;
+            let mut cumulative = 0u64;
+            for (i, count) in merged.iter().enumerate() {
+                cumulative += count;
+                if cumulative >= target {
+                    return *LATENCY_BUCKETS_MICROS
+                        .get(i)
+                        .unwrap_or_else(|| LATENCY_BUCKETS_MICROS.last().unwrap());
+                }
+            }
+            *LATENCY_BUCKETS_MICROS.last().unwrap()
+        };
+
+        LatencyPercentiles {
+            p50: percentile(0.50),
+            p90: percentile(0.90),
+            p99: percentile(0.99),
+        }
+    }
+}
+
+/// Counters shared between `ProgramFilterPlugin` and the stats-reporting
+/// task, so progress can be read back without owning the plugin (the runner
+/// takes ownership of it via `Box<dyn Plugin>`).
+struct PluginStats {
     tx_count: AtomicU64,
     match_count: AtomicU64,
     last_slot: AtomicU64,
+    /// Highest slot *completed* so far, per firehose thread. Indexed by
+    /// `thread_id`. The watermark that is safe to checkpoint is the min
+    /// across all of these, since threads process slots out of order.
+    thread_watermarks: Vec<AtomicU64>,
+    /// Slot each thread is currently in the middle of processing, indexed by
+    /// `thread_id`. `u64::MAX` means the thread hasn't processed anything
+    /// yet. Used to detect when a thread moves on to a new slot, at which
+    /// point the slot it just left is known to be complete and can advance
+    /// `thread_watermarks`.
+    thread_current_slot: Vec<AtomicU64>,
+    /// Lowest slot each thread was ever seen processing, indexed by
+    /// `thread_id`. `u64::MAX` means the thread hasn't processed anything
+    /// yet. Combined with `thread_current_slot`, this gives each thread's
+    /// observed `[first, last]` range so `finalize_coverage` can close out
+    /// the chunk-boundary/tail gaps that `observe_transaction_slot` alone
+    /// can't (it only closes gaps *between* two transactions on the same
+    /// thread).
+    thread_first_slot: Vec<AtomicU64>,
+    latency: LatencyHistogram,
+    coverage: SlotCoverage,
 }
 
-impl ProgramFilterPlugin {
-    fn new(program_id: &str) -> Self {
-        let bytes = bs58::decode(program_id)
-            .into_vec()
-            .expect("Invalid base58 program ID");
-        let mut arr = [0u8; 32];
-        arr.copy_from_slice(&bytes[..32]);
+impl PluginStats {
+    fn new(threads: usize, start_slot: u64, end_slot: u64) -> Self {
         Self {
-            program_id_bytes: arr,
             tx_count: AtomicU64::new(0),
             match_count: AtomicU64::new(0),
             last_slot: AtomicU64::new(0),
+            thread_watermarks: (0..threads).map(|_| AtomicU64::new(0)).collect(),
+            thread_current_slot: (0..threads).map(|_| AtomicU64::new(u64::MAX)).collect(),
+            thread_first_slot: (0..threads).map(|_| AtomicU64::new(u64::MAX)).collect(),
+            latency: LatencyHistogram::new(threads),
+            coverage: SlotCoverage::new(start_slot, end_slot),
+        }
+    }
+
+    /// Records that `thread_id` just saw a transaction for `slot`. If the
+    /// thread was previously in the middle of a *different* slot, that slot
+    /// (and anything between it and `slot`) is now known to be behind this
+    /// thread's progress, so it advances the watermark and marks the whole
+    /// span as covered (see `SlotCoverage`'s doc comment). Warns (rather
+    /// than silently doing nothing) if `thread_id` is outside the configured
+    /// `--threads` range, since that would otherwise leave the watermark
+    /// stuck at 0 for the whole run.
+    fn observe_transaction_slot(&self, thread_id: usize, slot: u64) {
+        let (Some(current), Some(watermark), Some(first_slot)) = (
+            self.thread_current_slot.get(thread_id),
+            self.thread_watermarks.get(thread_id),
+            self.thread_first_slot.get(thread_id),
+        ) else {
+            eprintln!(
+                "WARN: thread_id {} is out of range for {} configured threads; watermark tracking skipped for this transaction",
+                thread_id,
+                self.thread_watermarks.len()
+            );
+            return;
+        };
+        let previous = current.swap(slot, Ordering::Relaxed);
+        if previous == u64::MAX {
+            first_slot.store(slot, Ordering::Relaxed);
+        } else if previous != slot {
+            watermark.fetch_max(previous, Ordering::Relaxed);
+            let (low, high) = if previous < slot {
+                (previous, slot - 1)
+            } else {
+                (slot, previous - 1)
+            };
+            self.coverage.mark_range_seen(low, high);
+        }
+    }
+
+    /// Closes out coverage gaps that exist only because a thread's assigned
+    /// chunk ended on an empty tail with no later transaction on that thread
+    /// to trigger `observe_transaction_slot`'s range-closing logic — most
+    /// commonly the very end of the whole run (`end_slot` itself is often
+    /// empty), but also the boundary between two threads' adjacent chunks.
+    /// Call once `JetstreamerRunner::run_range` has returned successfully,
+    /// i.e. firehose has finished delivering the entire requested range.
+    ///
+    /// Threads are assumed to each own a contiguous, non-overlapping
+    /// sub-range of `[start_slot, end_slot]` — the same assumption
+    /// `commit_watermark` already relies on for checkpointing. Sorting each
+    /// thread's observed `[first, last]` range and closing the span between
+    /// one thread's last slot and the next thread's first slot (or
+    /// `end_slot` for the last range) turns those into known-complete spans
+    /// instead of reported gaps.
+    fn finalize_coverage(&self) {
+        let mut ranges: Vec<(u64, u64)> = self
+            .thread_first_slot
+            .iter()
+            .zip(self.thread_current_slot.iter())
+            .filter_map(|(first, last)| {
+                let first = first.load(Ordering::Relaxed);
+                let last = last.load(Ordering::Relaxed);
+                (first != u64::MAX).then_some((first, last))
+            })
+            .collect();
+        ranges.sort_unstable();
+
+        for (i, &(_, last)) in ranges.iter().enumerate() {
+            let next_first = ranges
+                .get(i + 1)
+                .map(|&(first, _)| first)
+                .unwrap_or(self.coverage.end_slot.saturating_add(1));
+            if next_first > last + 1 {
+                self.coverage.mark_range_seen(last + 1, next_first - 1);
+            }
+        }
+    }
+
+    /// The highest slot for which processing is complete across all threads.
+    fn commit_watermark(&self) -> u64 {
+        self.thread_watermarks
+            .iter()
+            .map(|w| w.load(Ordering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Tracks, as a lock-free bitset, which slots in `[start_slot, end_slot]`
+/// firehose is believed to have delivered (whether or not any of them
+/// contained a transaction). Because firehose delivers slots out of order
+/// across threads, a backfill can silently skip slots if Old Faithful
+/// returns incomplete data for them; this lets `async_main` report any gaps
+/// once the run finishes.
+///
+/// `jetstreamer::plugin::Plugin` only calls back per-transaction, with no
+/// per-slot "delivered, zero transactions" signal, and a large fraction of
+/// real Solana slots are legitimately empty. To avoid flagging every empty
+/// slot as a gap, `mark_range_seen` is used (via `PluginStats::observe_transaction_slot`)
+/// to mark the whole span a thread's current slot advances through as seen,
+/// not just the literal slots transactions landed in — this assumes each
+/// thread is handed a contiguous sub-range of slots to advance through
+/// monotonically (the same assumption `commit_watermark` already relies on
+/// for checkpointing). It is a heuristic, not a confirmed-delivery signal:
+/// if jetstreamer exposes a dedicated per-slot hook in the future, coverage
+/// should be driven from that instead.
+struct SlotCoverage {
+    start_slot: u64,
+    end_slot: u64,
+    seen: Vec<AtomicU64>,
+}
+
+impl SlotCoverage {
+    fn new(start_slot: u64, end_slot: u64) -> Self {
+        let slot_count = end_slot.saturating_sub(start_slot) + 1;
+        let words = ((slot_count + 63) / 64) as usize;
+        Self {
+            start_slot,
+            end_slot,
+            seen: (0..words).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn mark_seen(&self, slot: u64) {
+        if slot < self.start_slot || slot > self.end_slot {
+            return;
+        }
+        let index = slot - self.start_slot;
+        let word = (index / 64) as usize;
+        let bit = index % 64;
+        if let Some(w) = self.seen.get(word) {
+            w.fetch_or(1u64 << bit, Ordering::Relaxed);
+        }
+    }
+
+    /// Marks every slot in `[from, to]` (clamped to `[start_slot, end_slot]`)
+    /// as seen. See the `SlotCoverage` doc comment for why whole ranges, not
+    /// just individual transaction slots, get marked.
+    fn mark_range_seen(&self, from: u64, to: u64) {
+        let from = from.max(self.start_slot);
+        let to = to.min(self.end_slot);
+        let mut slot = from;
+        while slot <= to {
+            self.mark_seen(slot);
+            slot += 1;
+        }
+    }
+
+    fn is_seen(&self, slot: u64) -> bool {
+        let index = slot - self.start_slot;
+        let word = (index / 64) as usize;
+        let bit = index % 64;
+        self.seen
+            .get(word)
+            .map(|w| w.load(Ordering::Relaxed) & (1u64 << bit) != 0)
+            .unwrap_or(false)
+    }
+
+    /// Contiguous `[start, end]` slot ranges within `[start_slot, end_slot]`
+    /// that were never seen, plus the observed/expected slot counts.
+    fn gaps(&self) -> (Vec<(u64, u64)>, u64, u64) {
+        let expected = self.end_slot.saturating_sub(self.start_slot) + 1;
+        let mut observed = 0u64;
+        let mut gaps = Vec::new();
+        let mut gap_start: Option<u64> = None;
+
+        for slot in self.start_slot..=self.end_slot {
+            if self.is_seen(slot) {
+                observed += 1;
+                if let Some(start) = gap_start.take() {
+                    gaps.push((start, slot - 1));
+                }
+            } else if gap_start.is_none() {
+                gap_start = Some(slot);
+            }
+        }
+        if let Some(start) = gap_start {
+            gaps.push((start, self.end_slot));
+        }
+
+        (gaps, observed, expected)
+    }
+}
+
+/// Plugin that filters transactions per `Filter` and hands matching ones to
+/// the output sink writer task
+struct ProgramFilterPlugin {
+    filter: Filter,
+    /// Base58 encoding of each address in `Filter::Mentions { programs, .. }`
+    /// only (deliberately excludes `mentions` — see that field's doc
+    /// comment), precomputed so the log-content check doesn't re-encode on
+    /// every transaction.
+    program_b58: Vec<String>,
+    stats: Arc<PluginStats>,
+    checkpoint_path: Option<PathBuf>,
+    sink_tx: tokio::sync::mpsc::UnboundedSender<OutputRecord>,
+    event_decoder: Option<EventDecoder>,
+}
+
+impl ProgramFilterPlugin {
+    fn new(
+        filter: Filter,
+        stats: Arc<PluginStats>,
+        checkpoint_path: Option<PathBuf>,
+        sink_tx: tokio::sync::mpsc::UnboundedSender<OutputRecord>,
+        event_decoder: Option<EventDecoder>,
+    ) -> Self {
+        let program_b58 = match &filter {
+            Filter::Mentions { programs, .. } => programs
+                .iter()
+                .map(|addr| bs58::encode(addr).into_string())
+                .collect(),
+            Filter::All | Filter::AllWithVotes => Vec::new(),
+        };
+        Self {
+            filter,
+            program_b58,
+            stats,
+            checkpoint_path,
+            sink_tx,
+            event_decoder,
         }
     }
 }
@@ -83,74 +1060,120 @@ impl Plugin for ProgramFilterPlugin {
 
     fn on_transaction<'a>(
         &'a self,
-        _thread_id: usize,
+        thread_id: usize,
         _db: Option<Arc<clickhouse::Client>>,
         transaction: &'a TransactionData,
     ) -> PluginFuture<'a> {
         async move {
-            let count = self.tx_count.fetch_add(1, Ordering::Relaxed);
-            self.last_slot.store(transaction.slot, Ordering::Relaxed);
-
-            // Print progress to stderr every 100k transactions
-            if count > 0 && count % 100_000 == 0 {
-                let matches = self.match_count.load(Ordering::Relaxed);
-                let slot = self.last_slot.load(Ordering::Relaxed);
-                eprintln!(
-                    "PROGRESS:{{\"processed\":{},\"matched\":{},\"currentSlot\":{}}}",
-                    count, matches, slot
-                );
-            }
+            let started_at = std::time::Instant::now();
+            let result = async {
+                let count = self.stats.tx_count.fetch_add(1, Ordering::Relaxed);
+                self.stats.last_slot.store(transaction.slot, Ordering::Relaxed);
+                self.stats.coverage.mark_seen(transaction.slot);
+                self.stats
+                    .observe_transaction_slot(thread_id, transaction.slot);
 
-            // Skip vote transactions
-            if transaction.is_vote {
-                return Ok(());
-            }
+                // Print progress to stderr every 100k transactions
+                if count > 0 && count % 100_000 == 0 {
+                    let matches = self.stats.match_count.load(Ordering::Relaxed);
+                    let slot = self.stats.last_slot.load(Ordering::Relaxed);
+                    eprintln!(
+                        "PROGRESS:{{\"processed\":{},\"matched\":{},\"currentSlot\":{}}}",
+                        count, matches, slot
+                    );
 
-            // Check if this transaction involves our program
-            let msg = &transaction.transaction.message;
-            let account_keys = msg.static_account_keys();
-            let program_involved = account_keys.iter().any(|key| {
-                key.to_bytes() == self.program_id_bytes
-            });
+                    if let Some(path) = &self.checkpoint_path {
+                        let checkpoint = Checkpoint {
+                            watermark_slot: self.stats.commit_watermark(),
+                            processed: count,
+                            matched: matches,
+                        };
+                        if let Err(err) = checkpoint.save(path) {
+                            eprintln!("WARN: failed to write checkpoint to {:?}: {}", path, err);
+                        }
+                    }
+                }
 
-            if !program_involved {
-                return Ok(());
-            }
+                // Skip vote transactions, unless the caller explicitly asked for them
+                if transaction.is_vote && !matches!(self.filter, Filter::AllWithVotes) {
+                    return Ok(());
+                }
 
-            // Extract log messages
-            let logs: Vec<String> = transaction
-                .transaction_status_meta
-                .log_messages
-                .as_ref()
-                .map(|msgs| msgs.clone())
-                .unwrap_or_default();
+                // Check if this transaction matches our filter. `matched_program`
+                // tracks whether a `--program` address (as opposed to only a
+                // `--mentions` address) was involved, since only `--program`
+                // addresses get the additional log-text check below.
+                let mut matched_program = false;
+                let mut matched_mention = false;
+                if let Filter::Mentions { programs, mentions } = &self.filter {
+                    let msg = &transaction.transaction.message;
+                    let account_keys = msg.static_account_keys();
+                    (matched_program, matched_mention) = classify_mentions_match(
+                        programs,
+                        mentions,
+                        account_keys.iter().map(|key| key.to_bytes()),
+                    );
 
-            // Only emit if there are logs (events come from logs)
-            if logs.is_empty() {
-                return Ok(());
-            }
+                    if !matched_program && !matched_mention {
+                        return Ok(());
+                    }
+                }
 
-            // Check if any log references our program (additional filter)
-            let program_b58 = bs58::encode(&self.program_id_bytes).into_string();
-            let has_program_log = logs.iter().any(|log| log.contains(&program_b58));
-            if !has_program_log {
-                return Ok(());
-            }
+                // Extract log messages
+                let logs: Vec<String> = transaction
+                    .transaction_status_meta
+                    .log_messages
+                    .as_ref()
+                    .map(|msgs| msgs.clone())
+                    .unwrap_or_default();
 
-            self.match_count.fetch_add(1, Ordering::Relaxed);
+                // Only emit if there are logs (events come from logs)
+                if logs.is_empty() {
+                    return Ok(());
+                }
 
-            let record = OutputRecord {
-                signature: transaction.signature.to_string(),
-                slot: transaction.slot,
-                block_time: transaction.block_time,
-                logs,
-            };
+                // For `--program`-only matches, also require a log line that
+                // references one of the matched program IDs (see
+                // `Filter::Mentions`'s doc comment for why this doesn't
+                // apply to `--mentions` matches).
+                if matched_program && !matched_mention && !self.program_b58.is_empty() {
+                    let has_program_log = logs
+                        .iter()
+                        .any(|log| self.program_b58.iter().any(|b58| log.contains(b58)));
+                    if !has_program_log {
+                        return Ok(());
+                    }
+                }
 
-            // Write NDJSON to stdout (thread-safe via println!)
-            let json = serde_json::to_string(&record).unwrap();
-            println!("{}", json);
+                self.stats.match_count.fetch_add(1, Ordering::Relaxed);
 
-            Ok(())
+                let events = self
+                    .event_decoder
+                    .as_ref()
+                    .map(|decoder| decoder.decode_logs(&logs))
+                    .unwrap_or_default();
+
+                let record = OutputRecord {
+                    signature: transaction.signature.to_string(),
+                    slot: transaction.slot,
+                    block_time: transaction.block_time,
+                    logs,
+                    events,
+                };
+
+                // Hand off to the single writer task instead of writing here,
+                // so concurrent firehose threads never contend on stdout's lock.
+                if self.sink_tx.send(record).is_err() {
+                    eprintln!("WARN: output sink writer task has shut down, dropping record");
+                }
+
+                Ok(())
+            }
+            .await;
+
+            let elapsed_micros = started_at.elapsed().as_micros() as u64;
+            self.stats.latency.record(thread_id, elapsed_micros);
+            result
         }
         .boxed()
     }
@@ -158,6 +1181,7 @@ impl Plugin for ProgramFilterPlugin {
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    let filter = Filter::from_args(&args)?;
 
     // SAFETY: set env vars before any threads are spawned (before tokio runtime)
     unsafe {
@@ -166,29 +1190,620 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     eprintln!(
-        "Starting uho-backfill: program={}, slots={}..{}, threads={}",
-        args.program, args.start_slot, args.end_slot, args.threads
+        "Starting uho-backfill: filter={}, slots={}..{}, threads={}",
+        describe_filter(&filter),
+        args.start_slot,
+        args.end_slot,
+        args.threads
     );
 
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()?
-        .block_on(async_main(args))
+        .block_on(async_main(args, filter))
 }
 
-async fn async_main(args: Args) -> Result<(), Box<dyn std::error::Error>> {
+/// Human-readable summary of the active filter, for the startup banner.
+fn describe_filter(filter: &Filter) -> String {
+    match filter {
+        Filter::All => "all".to_string(),
+        Filter::AllWithVotes => "allWithVotes".to_string(),
+        Filter::Mentions { programs, mentions } => {
+            format!(
+                "mentions({} programs, {} mentions)",
+                programs.len(),
+                mentions.len()
+            )
+        }
+    }
+}
 
-    let plugin = ProgramFilterPlugin::new(&args.program);
+async fn async_main(args: Args, filter: Filter) -> Result<(), Box<dyn std::error::Error>> {
+    let mut start_slot = args.start_slot;
+    if let Some(path) = &args.checkpoint {
+        if let Some(checkpoint) = Checkpoint::load(path) {
+            eprintln!(
+                "Resuming from checkpoint: watermark_slot={}, processed={}, matched={}",
+                checkpoint.watermark_slot, checkpoint.processed, checkpoint.matched
+            );
+            start_slot = (checkpoint.watermark_slot + 1).max(start_slot);
+        }
+    }
+
+    let mut sinks: Vec<Box<dyn OutputSink>> = Vec::new();
+    match args.output {
+        OutputFormat::Ndjson => sinks.push(Box::new(NdjsonStdoutSink::new())),
+        OutputFormat::Csv => {
+            let path = args
+                .out_file
+                .as_ref()
+                .ok_or("--out-file is required when --output csv is set")?;
+            sinks.push(Box::new(CsvFileSink::create(path).await?));
+        }
+    }
+    if let Some(socket_path) = &args.socket {
+        sinks.push(Box::new(SocketSink::bind(socket_path).await?));
+    }
+    let (sink_tx, sink_writer) = spawn_sink_writer(sinks);
+
+    let event_decoder = match &args.idl {
+        Some(path) => Some(EventDecoder::load(path)?),
+        None => None,
+    };
+
+    let stats = Arc::new(PluginStats::new(args.threads, start_slot, args.end_slot));
+    spawn_stats_reporter(stats.clone(), start_slot, args.end_slot, args.stats_interval_secs);
+
+    let plugin = ProgramFilterPlugin::new(
+        filter,
+        stats.clone(),
+        args.checkpoint.clone(),
+        sink_tx,
+        event_decoder,
+    );
 
     // Build the runner with our slot range
-    let slot_range = format!("{}:{}", args.start_slot, args.end_slot);
+    let slot_range = format!("{}:{}", start_slot, args.end_slot);
 
     let mut runner = JetstreamerRunner::new();
     runner.register_plugin(Box::new(plugin));
     runner.run_range(&slot_range).await?;
 
-    // Final stats
+    // The run finished successfully: close out each thread's trailing empty
+    // span (and the gaps between adjacent threads' chunks) before reporting,
+    // since those can no longer be closed by a later transaction arriving.
+    stats.finalize_coverage();
+
+    // Drop the plugin (and with it, its `sink_tx` clone) so the writer task's
+    // channel closes and it drains any already-queued records instead of
+    // being torn down mid-write when the process exits.
+    drop(runner);
+    if let Err(err) = sink_writer.await {
+        eprintln!("WARN: output sink writer task panicked: {}", err);
+    }
+
+    report_gaps(&stats.coverage);
+
     eprintln!("DONE:{{\"status\":\"completed\"}}");
 
     Ok(())
 }
+
+/// Emits a `GAPS:` integrity report comparing the slots firehose is believed
+/// to have delivered (see `SlotCoverage` for how that is inferred) against the full `[start_slot, end_slot]` range, so a backfill
+/// can be trusted as complete (or not) before it's loaded downstream.
+fn report_gaps(coverage: &SlotCoverage) {
+    let (gaps, observed, expected) = coverage.gaps();
+    let gaps_json: Vec<String> = gaps
+        .iter()
+        .map(|(start, end)| format!("[{},{}]", start, end))
+        .collect();
+    eprintln!(
+        "GAPS:{{\"expectedSlots\":{},\"observedSlots\":{},\"missingRanges\":[{}]}}",
+        expected,
+        observed,
+        gaps_json.join(",")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(ty: &str) -> IdlFieldType {
+        IdlFieldType::Named(ty.to_string())
+    }
+
+    #[test]
+    fn decode_field_primitives_roundtrip() {
+        let mut cursor: &[u8] = &[1, 42, 0x05, 0x00];
+        assert_eq!(
+            decode_field(&named("bool"), &mut cursor),
+            Some(serde_json::Value::Bool(true))
+        );
+        assert_eq!(
+            decode_field(&named("u8"), &mut cursor),
+            Some(serde_json::json!(42))
+        );
+        assert_eq!(
+            decode_field(&named("u16"), &mut cursor),
+            Some(serde_json::json!(5u16))
+        );
+        assert!(cursor.is_empty());
+    }
+
+    #[test]
+    fn decode_field_u64_is_stringified_to_avoid_precision_loss() {
+        let mut cursor: &[u8] = &u64::MAX.to_le_bytes();
+        assert_eq!(
+            decode_field(&named("u64"), &mut cursor),
+            Some(serde_json::Value::String(u64::MAX.to_string()))
+        );
+    }
+
+    #[test]
+    fn decode_field_string_reads_u32_len_prefix() {
+        let mut bytes = 5u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(b"hello");
+        let mut cursor: &[u8] = &bytes;
+        assert_eq!(
+            decode_field(&named("string"), &mut cursor),
+            Some(serde_json::Value::String("hello".to_string()))
+        );
+        assert!(cursor.is_empty());
+    }
+
+    #[test]
+    fn decode_field_public_key_encodes_base58() {
+        let key = [7u8; 32];
+        let mut cursor: &[u8] = &key;
+        let expected = bs58::encode(key).into_string();
+        assert_eq!(
+            decode_field(&named("publicKey"), &mut cursor),
+            Some(serde_json::Value::String(expected))
+        );
+        assert!(cursor.is_empty());
+    }
+
+    #[test]
+    fn decode_field_truncated_input_returns_none() {
+        let mut cursor: &[u8] = &[1, 2];
+        assert_eq!(decode_field(&named("u32"), &mut cursor), None);
+    }
+
+    #[test]
+    fn decode_field_unsupported_type_returns_none() {
+        let mut cursor: &[u8] = &[1, 2, 3];
+        let ty = IdlFieldType::Other(serde_json::json!({"vec": "u8"}));
+        assert_eq!(decode_field(&ty, &mut cursor), None);
+    }
+
+    #[test]
+    fn decode_event_surfaces_partial_on_unsupported_field() {
+        let decoder = EventDecoder {
+            events_by_discriminator: std::collections::HashMap::from([(
+                [1u8; 8],
+                IdlEvent {
+                    name: "Thing".to_string(),
+                    discriminator: Some([1u8; 8]),
+                    fields: vec![
+                        IdlField {
+                            name: "a".to_string(),
+                            ty: named("u8"),
+                        },
+                        IdlField {
+                            name: "b".to_string(),
+                            ty: IdlFieldType::Other(serde_json::json!({"vec": "u8"})),
+                        },
+                    ],
+                },
+            )]),
+        };
+        let mut bytes = vec![1u8; 8];
+        bytes.push(9); // "a"
+        bytes.extend_from_slice(&[1, 2, 3]); // leftover for unsupported "b"
+
+        let decoded = decoder.decode_event(&bytes).expect("discriminator matches");
+        assert_eq!(decoded.name, "Thing");
+        assert!(decoded.partial);
+        assert_eq!(decoded.fields["a"], serde_json::json!(9));
+        assert_eq!(decoded.fields["b_raw"], serde_json::json!("010203"));
+    }
+
+    #[test]
+    fn anchor_event_sighash_is_used_when_discriminator_missing() {
+        let data = r#"{"events":[{"name":"MyEvent","fields":[]}]}"#;
+        let idl: Idl = serde_json::from_str(data).unwrap();
+        let event = &idl.events[0];
+        assert_eq!(event.discriminator, None);
+
+        let path = std::env::temp_dir().join(format!(
+            "uho-backfill-test-idl-sighash-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, data).expect("write should succeed");
+        let decoder = EventDecoder::load(&path).expect("IDL should load");
+        fs::remove_file(&path).ok();
+        assert_eq!(
+            decoder
+                .events_by_discriminator
+                .get(&anchor_event_sighash("MyEvent"))
+                .map(|event| event.name.as_str()),
+            Some("MyEvent")
+        );
+    }
+
+    #[test]
+    fn csv_escape_wraps_plain_field_in_quotes() {
+        assert_eq!(csv_escape("hello"), "\"hello\"");
+    }
+
+    #[test]
+    fn csv_escape_doubles_embedded_quotes() {
+        assert_eq!(csv_escape(r#"say "hi""#), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn csv_escape_leaves_commas_and_newlines_inside_quotes() {
+        assert_eq!(csv_escape("a,b\nc"), "\"a,b\nc\"");
+    }
+
+    fn histogram_with_samples(threads: usize, samples: &[(usize, u64)]) -> LatencyHistogram {
+        let histogram = LatencyHistogram::new(threads);
+        for &(thread_id, micros) in samples {
+            histogram.record(thread_id, micros);
+        }
+        histogram
+    }
+
+    #[test]
+    fn latency_percentiles_empty_histogram_is_zero() {
+        let histogram = LatencyHistogram::new(2);
+        let p = histogram.percentiles();
+        assert_eq!((p.p50, p.p90, p.p99), (0, 0, 0));
+    }
+
+    #[test]
+    fn latency_percentiles_merge_across_threads() {
+        // 100 samples at 50us, all within the smallest bucket (<= 50us).
+        let samples: Vec<(usize, u64)> = (0..100).map(|i| (i % 2, 50)).collect();
+        let histogram = histogram_with_samples(2, &samples);
+        let p = histogram.percentiles();
+        assert_eq!((p.p50, p.p90, p.p99), (50, 50, 50));
+    }
+
+    #[test]
+    fn latency_percentiles_fall_in_correct_bucket() {
+        // One sample in each of the first three buckets' upper bounds.
+        let histogram = histogram_with_samples(1, &[(0, 50), (0, 100), (0, 250)]);
+        let p = histogram.percentiles();
+        // 3 samples: p50 -> 2nd sample's bucket (100), p99 -> 3rd (250).
+        assert_eq!(p.p50, 100);
+        assert_eq!(p.p99, 250);
+    }
+
+    #[test]
+    fn latency_record_out_of_range_thread_id_does_not_panic() {
+        let histogram = LatencyHistogram::new(1);
+        histogram.record(5, 100);
+        let p = histogram.percentiles();
+        assert_eq!((p.p50, p.p90, p.p99), (0, 0, 0));
+    }
+
+    #[test]
+    fn slot_coverage_starts_with_whole_range_reported_as_one_gap() {
+        let coverage = SlotCoverage::new(100, 109);
+        let (gaps, observed, expected) = coverage.gaps();
+        assert_eq!(expected, 10);
+        assert_eq!(observed, 0);
+        assert_eq!(gaps, vec![(100, 109)]);
+    }
+
+    #[test]
+    fn slot_coverage_mark_seen_leaves_a_single_slot_gap() {
+        let coverage = SlotCoverage::new(100, 109);
+        for slot in [100, 101, 102, 104, 105, 106, 107, 108, 109] {
+            coverage.mark_seen(slot);
+        }
+        let (gaps, observed, expected) = coverage.gaps();
+        assert_eq!(expected, 10);
+        assert_eq!(observed, 9);
+        assert_eq!(gaps, vec![(103, 103)]);
+    }
+
+    #[test]
+    fn slot_coverage_mark_range_seen_fills_a_contiguous_span() {
+        let coverage = SlotCoverage::new(0, 199);
+        coverage.mark_range_seen(10, 20);
+        assert!(coverage.is_seen(10));
+        assert!(coverage.is_seen(20));
+        assert!(!coverage.is_seen(9));
+        assert!(!coverage.is_seen(21));
+        let (gaps, observed, _) = coverage.gaps();
+        assert_eq!(observed, 11);
+        assert_eq!(gaps, vec![(0, 9), (21, 199)]);
+    }
+
+    #[test]
+    fn slot_coverage_mark_range_seen_clamps_to_bounds() {
+        let coverage = SlotCoverage::new(100, 109);
+        coverage.mark_range_seen(0, 105);
+        coverage.mark_range_seen(108, 500);
+        let (gaps, observed, _) = coverage.gaps();
+        assert_eq!(observed, 8);
+        assert_eq!(gaps, vec![(106, 107)]);
+    }
+
+    #[test]
+    fn slot_coverage_mark_seen_out_of_range_is_ignored() {
+        let coverage = SlotCoverage::new(100, 109);
+        coverage.mark_seen(5);
+        coverage.mark_seen(1_000);
+        let (gaps, observed, expected) = coverage.gaps();
+        assert_eq!(observed, 0);
+        assert_eq!(expected, 10);
+        assert_eq!(gaps, vec![(100, 109)]);
+    }
+
+    #[test]
+    fn finalize_coverage_closes_trailing_empty_tail_at_end_of_run() {
+        // Single thread's last transaction lands well before end_slot; the
+        // remaining slots are legitimately empty and never followed by
+        // another transaction on that thread.
+        let stats = PluginStats::new(1, 100, 109);
+        stats.observe_transaction_slot(0, 100);
+        stats.observe_transaction_slot(0, 103);
+        stats.coverage.mark_seen(103);
+
+        let (gaps, _, _) = stats.coverage.gaps();
+        assert_eq!(gaps, vec![(104, 109)], "tail should look unclosed before finalize");
+
+        stats.finalize_coverage();
+        let (gaps, observed, expected) = stats.coverage.gaps();
+        assert!(gaps.is_empty());
+        assert_eq!(observed, expected);
+    }
+
+    #[test]
+    fn finalize_coverage_closes_gap_between_adjacent_thread_chunks() {
+        // Two threads each own a contiguous chunk; thread 0's chunk ends on
+        // an empty tail and thread 1's chunk starts a few slots later, with
+        // nothing ever observed in between.
+        let stats = PluginStats::new(2, 100, 119);
+        stats.observe_transaction_slot(0, 100);
+        stats.observe_transaction_slot(0, 102);
+        stats.coverage.mark_seen(102);
+        stats.observe_transaction_slot(1, 115);
+        stats.observe_transaction_slot(1, 118);
+        stats.coverage.mark_seen(118);
+
+        stats.finalize_coverage();
+        let (gaps, observed, expected) = stats.coverage.gaps();
+        assert!(gaps.is_empty(), "gaps: {:?}", gaps);
+        assert_eq!(observed, expected);
+    }
+
+    #[test]
+    fn finalize_coverage_does_not_touch_threads_that_never_ran() {
+        // A thread that never processed anything has no observed range, so
+        // finalize_coverage must skip it rather than treat its `u64::MAX`
+        // "not yet seen" marker as a range.
+        let stats = PluginStats::new(2, 100, 109);
+        stats.observe_transaction_slot(0, 100);
+        stats.observe_transaction_slot(0, 102);
+        stats.coverage.mark_seen(102);
+
+        stats.finalize_coverage();
+        // Thread 1 never ran, so its "range" doesn't exist; the only gap
+        // closed is thread 0's own trailing tail up to end_slot.
+        let (gaps, _, _) = stats.coverage.gaps();
+        assert!(gaps.is_empty(), "gaps: {:?}", gaps);
+    }
+
+    #[test]
+    fn checkpoint_save_then_load_roundtrips() {
+        let checkpoint = Checkpoint {
+            watermark_slot: 12345,
+            processed: 999,
+            matched: 17,
+        };
+        let path = std::env::temp_dir().join(format!(
+            "uho-backfill-test-checkpoint-roundtrip-{}.json",
+            std::process::id()
+        ));
+        checkpoint.save(&path).expect("save should succeed");
+
+        let loaded = Checkpoint::load(&path).expect("load should find the saved checkpoint");
+        assert_eq!(loaded.watermark_slot, checkpoint.watermark_slot);
+        assert_eq!(loaded.processed, checkpoint.processed);
+        assert_eq!(loaded.matched, checkpoint.matched);
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn checkpoint_load_returns_none_for_missing_file() {
+        let path = std::env::temp_dir().join(format!(
+            "uho-backfill-test-checkpoint-missing-{}.json",
+            std::process::id()
+        ));
+        fs::remove_file(&path).ok();
+        assert!(Checkpoint::load(&path).is_none());
+    }
+
+    #[test]
+    fn checkpoint_load_returns_none_for_corrupt_file() {
+        let path = std::env::temp_dir().join(format!(
+            "uho-backfill-test-checkpoint-corrupt-{}.json",
+            std::process::id()
+        ));
+        fs::write(&path, b"not valid json").expect("write should succeed");
+
+        assert!(Checkpoint::load(&path).is_none());
+
+        fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn commit_watermark_is_zero_before_any_slot_is_observed() {
+        let stats = PluginStats::new(2, 100, 199);
+        assert_eq!(stats.commit_watermark(), 0);
+    }
+
+    #[test]
+    fn commit_watermark_is_the_min_across_threads_processing_out_of_order() {
+        let stats = PluginStats::new(2, 100, 199);
+        // Thread 0 races ahead; thread 1 is still behind.
+        stats.observe_transaction_slot(0, 150);
+        stats.observe_transaction_slot(0, 160);
+        stats.observe_transaction_slot(1, 101);
+        stats.observe_transaction_slot(1, 102);
+        // Thread 0's watermark is now 150 (it left that slot behind), but
+        // thread 1's is only 101 — commit_watermark must report the min, not
+        // whichever thread last stored a slot, since that's the highest slot
+        // known-complete across *every* thread.
+        assert_eq!(stats.commit_watermark(), 101);
+    }
+
+    #[test]
+    fn observe_transaction_slot_ignores_out_of_range_thread_id_without_panicking() {
+        let stats = PluginStats::new(1, 100, 199);
+        stats.observe_transaction_slot(5, 150);
+        assert_eq!(stats.commit_watermark(), 0);
+    }
+
+    #[test]
+    fn filter_from_args_all_with_votes_takes_precedence_over_everything() {
+        let args = Args {
+            program: vec!["11111111111111111111111111111111".to_string()],
+            mentions: vec![],
+            all: true,
+            all_with_votes: true,
+            start_slot: 0,
+            end_slot: 0,
+            threads: 1,
+            checkpoint: None,
+            output: OutputFormat::Ndjson,
+            out_file: None,
+            socket: None,
+            idl: None,
+            stats_interval_secs: 30,
+        };
+        assert!(matches!(
+            Filter::from_args(&args).unwrap(),
+            Filter::AllWithVotes
+        ));
+    }
+
+    #[test]
+    fn filter_from_args_all_takes_precedence_over_program_and_mentions() {
+        let args = Args {
+            program: vec!["11111111111111111111111111111111".to_string()],
+            mentions: vec![],
+            all: true,
+            all_with_votes: false,
+            start_slot: 0,
+            end_slot: 0,
+            threads: 1,
+            checkpoint: None,
+            output: OutputFormat::Ndjson,
+            out_file: None,
+            socket: None,
+            idl: None,
+            stats_interval_secs: 30,
+        };
+        assert!(matches!(Filter::from_args(&args).unwrap(), Filter::All));
+    }
+
+    #[test]
+    fn filter_from_args_errors_when_nothing_is_specified() {
+        let args = Args {
+            program: vec![],
+            mentions: vec![],
+            all: false,
+            all_with_votes: false,
+            start_slot: 0,
+            end_slot: 0,
+            threads: 1,
+            checkpoint: None,
+            output: OutputFormat::Ndjson,
+            out_file: None,
+            socket: None,
+            idl: None,
+            stats_interval_secs: 30,
+        };
+        assert!(Filter::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn filter_from_args_splits_program_and_mentions_into_separate_lists() {
+        let program_addr = decode_address("11111111111111111111111111111111").unwrap();
+        let mentions_addr = decode_address("So11111111111111111111111111111111111111112").unwrap();
+        let args = Args {
+            program: vec!["11111111111111111111111111111111".to_string()],
+            mentions: vec!["So11111111111111111111111111111111111111112".to_string()],
+            all: false,
+            all_with_votes: false,
+            start_slot: 0,
+            end_slot: 0,
+            threads: 1,
+            checkpoint: None,
+            output: OutputFormat::Ndjson,
+            out_file: None,
+            socket: None,
+            idl: None,
+            stats_interval_secs: 30,
+        };
+        match Filter::from_args(&args).unwrap() {
+            Filter::Mentions { programs, mentions } => {
+                assert_eq!(programs, vec![program_addr]);
+                assert_eq!(mentions, vec![mentions_addr]);
+            }
+            _ => panic!("expected Filter::Mentions"),
+        }
+    }
+
+    #[test]
+    fn decode_address_rejects_invalid_base58() {
+        assert!(decode_address("not-valid-base58!!!").is_err());
+    }
+
+    #[test]
+    fn decode_address_rejects_wrong_length() {
+        // Valid base58, but decodes to fewer than 32 bytes.
+        assert!(decode_address("11111111111111111111111111").is_err());
+    }
+
+    #[test]
+    fn classify_mentions_match_matches_on_program_only() {
+        let program = [1u8; 32];
+        let other = [2u8; 32];
+        let (matched_program, matched_mention) =
+            classify_mentions_match(&[program], &[other], [program, [9u8; 32]].into_iter());
+        assert!(matched_program);
+        assert!(!matched_mention);
+    }
+
+    #[test]
+    fn classify_mentions_match_matches_on_mention_only() {
+        let program = [1u8; 32];
+        let mention = [2u8; 32];
+        let (matched_program, matched_mention) =
+            classify_mentions_match(&[program], &[mention], [mention, [9u8; 32]].into_iter());
+        assert!(!matched_program);
+        assert!(matched_mention);
+    }
+
+    #[test]
+    fn classify_mentions_match_returns_false_false_when_neither_present() {
+        let program = [1u8; 32];
+        let mention = [2u8; 32];
+        let (matched_program, matched_mention) = classify_mentions_match(
+            &[program],
+            &[mention],
+            [[7u8; 32], [8u8; 32]].into_iter(),
+        );
+        assert!(!matched_program);
+        assert!(!matched_mention);
+    }
+}